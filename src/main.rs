@@ -1,7 +1,12 @@
 use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
     env,
-    fs::{self, File},
-    io::Read,
+    fs,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use reqwest::Client;
@@ -10,6 +15,11 @@ use serde::{Deserialize, Serialize};
 const URL_LLM: &str = "http://localhost:11434/api/generate";
 const MODEL_LLM: &str = "llama3:instruct";
 const PATH_TO_DIR: &str = "./notes";
+const NOTE_DELIMITER: &str = "===\n---\n===\n";
+const MAX_CONTEXT_TOKENS: usize = 2048;
+const MAX_DEPTH: usize = 8;
+const TOP_K: usize = 5;
+const EMBED_CACHE_FILE: &str = ".embeddings_cache.json";
 
 #[derive(Debug, Deserialize, Serialize)]
 struct LLMResponse {
@@ -27,6 +37,52 @@ struct LLMResponse {
     err: Option<String>,
 }
 
+/// A single line of Ollama's NDJSON streaming response. `model`, `created_at`,
+/// `response` and `done` are present on every line; the timing/context fields
+/// are filled in on the final `done: true` line, so those are defaulted here.
+#[derive(Debug, Deserialize, Serialize)]
+struct LLMPartial {
+    model: String,
+    created_at: String,
+    response: String,
+    done: bool,
+    #[serde(default)]
+    context: Vec<i32>,
+    #[serde(default)]
+    total_duration: f64,
+    #[serde(default)]
+    load_duration: f64,
+    #[serde(default)]
+    prompt_eval_count: i32,
+    #[serde(default)]
+    prompt_eval_duration: f64,
+    #[serde(default)]
+    eval_count: i32,
+    #[serde(default)]
+    eval_duration: f64,
+    #[serde(default)]
+    err: Option<String>,
+}
+
+impl From<LLMPartial> for LLMResponse {
+    fn from(p: LLMPartial) -> LLMResponse {
+        LLMResponse {
+            model: p.model,
+            created_at: p.created_at,
+            response: p.response,
+            done: p.done,
+            context: p.context,
+            total_duration: p.total_duration,
+            load_duration: p.load_duration,
+            prompt_eval_count: p.prompt_eval_count,
+            prompt_eval_duration: p.prompt_eval_duration,
+            eval_count: p.eval_count,
+            eval_duration: p.eval_duration,
+            err: p.err,
+        }
+    }
+}
+
 struct Note {
     frontmatter: String,
     content: String,
@@ -55,43 +111,418 @@ impl Note {
     }
 }
 
-fn get_md_files(pathdir: &str) -> Vec<String> {
-    let mut files = Vec::new();
+/// Parse a note's frontmatter block as YAML, falling back to `Null` when it is
+/// empty or malformed.
+fn parse_frontmatter(frontmatter: &str) -> serde_yaml::Value {
+    if frontmatter.is_empty() {
+        return serde_yaml::Value::Null;
+    }
+    serde_yaml::from_str(frontmatter).unwrap_or(serde_yaml::Value::Null)
+}
+
+/// The string tags listed under the `tags:` key of a note's frontmatter.
+fn note_tags(note: &Note) -> Vec<String> {
+    parse_frontmatter(&note.frontmatter)
+        .get("tags")
+        .and_then(|v| v.as_sequence())
+        .map(|seq| seq.iter().filter_map(|v| v.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
+/// The note's `date:` (or `created:`) field, if present.
+fn note_date(note: &Note) -> Option<String> {
+    let fm = parse_frontmatter(&note.frontmatter);
+    fm.get("date")
+        .or_else(|| fm.get("created"))
+        .and_then(|v| v.as_str())
+        .map(String::from)
+}
+
+/// Parse a `YYYY-M-D` date into a comparable `(year, month, day)` tuple,
+/// tolerating non-zero-padded months and days (unlike a plain string compare).
+fn parse_ymd(date: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    Some((year, month, day))
+}
+
+/// A loader turns one file on disk into one or more [`Note`]s. Implementors are
+/// registered by extension so the directory walker can dispatch by file type.
+trait DocumentLoader {
+    fn load(&self, path: &Path) -> Result<Vec<Note>, Box<dyn std::error::Error>>;
+}
+
+/// Markdown loader: the original behavior, splitting YAML frontmatter from body.
+struct MarkdownLoader;
+
+impl DocumentLoader for MarkdownLoader {
+    fn load(&self, path: &Path) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(vec![Note::from_string(&content)])
+    }
+}
+
+/// Plain-text loader: the whole file is a single note with no frontmatter.
+struct TextLoader;
+
+impl DocumentLoader for TextLoader {
+    fn load(&self, path: &Path) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(vec![Note {
+            frontmatter: String::new(),
+            content,
+        }])
+    }
+}
+
+/// PDF loader: extract text page by page, emitting one note per page.
+struct PdfLoader;
+
+impl DocumentLoader for PdfLoader {
+    fn load(&self, path: &Path) -> Result<Vec<Note>, Box<dyn std::error::Error>> {
+        let doc = lopdf::Document::load(path)?;
+        let mut notes = Vec::new();
+        for &page_number in doc.get_pages().keys() {
+            let content = doc.extract_text(&[page_number])?;
+            notes.push(Note {
+                frontmatter: String::new(),
+                content,
+            });
+        }
+        Ok(notes)
+    }
+}
+
+/// Build the extension → loader registry. Extensions are matched lowercase.
+fn loader_registry() -> HashMap<String, Box<dyn DocumentLoader>> {
+    let mut registry: HashMap<String, Box<dyn DocumentLoader>> = HashMap::new();
+    registry.insert("md".to_string(), Box::new(MarkdownLoader));
+    registry.insert("txt".to_string(), Box::new(TextLoader));
+    registry.insert("pdf".to_string(), Box::new(PdfLoader));
+    registry
+}
+
+/// Walk a directory, dispatching each file to the loader for its extension.
+/// Unsupported files are skipped with a warning rather than a panic.
+fn load_notes(pathdir: &str) -> Vec<(String, Note)> {
+    let registry = loader_registry();
+    let mut notes = Vec::new();
     for entry in fs::read_dir(pathdir).unwrap() {
         let entry = entry.unwrap();
         let path = entry.path();
-        if path.is_file() {
-            let path_str = path.to_str().unwrap().to_string();
-            if path_str.ends_with(".md") {
-                files.push(path_str);
+        if !path.is_file() {
+            continue;
+        }
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        // Skip dotfiles such as the embedding cache sidecar.
+        if name.starts_with('.') {
+            continue;
+        }
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match registry.get(&ext) {
+            Some(loader) => match loader.load(&path) {
+                Ok(loaded) => {
+                    let path_str = path.to_str().unwrap().to_string();
+                    for note in loaded {
+                        notes.push((path_str.clone(), note));
+                    }
+                }
+                Err(e) => eprintln!("warning: failed to load {}: {}", path.display(), e),
+            },
+            None => eprintln!("warning: skipping unsupported file {}", path.display()),
+        }
+    }
+    notes
+}
+
+/// Response from Ollama's `/api/embeddings` endpoint.
+#[derive(Debug, Deserialize, Serialize)]
+struct EmbeddingResponse {
+    embedding: Vec<f64>,
+}
+
+/// An entry in the in-memory vector store: where the note came from, its
+/// embedding, and the note itself.
+struct EmbeddedNote {
+    path: String,
+    embedding: Vec<f64>,
+    note: Note,
+}
+
+/// Stable hex digest of a note's content, used to key the embedding cache so
+/// unchanged files are not re-embedded on re-runs.
+fn content_hash(content: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Embed a single piece of text via Ollama's `/api/embeddings` endpoint.
+async fn embed(
+    url_embed: &str,
+    model_llm: &str,
+    text: &str,
+) -> Result<Vec<f64>, Box<dyn std::error::Error>> {
+    let client = Client::new();
+    let res = client
+        .post(url_embed)
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "model": model_llm,
+            "prompt": text,
+        }))
+        .send()
+        .await?;
+
+    if res.status().is_success() {
+        let json_response: EmbeddingResponse = res.json().await?;
+        Ok(json_response.embedding)
+    } else {
+        Err("Failed to get embeddings from LLM".into())
+    }
+}
+
+/// Cosine similarity `dot(a, b) / (||a|| * ||b||)`, zero for a zero vector.
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// Load the embedding cache sidecar, returning an empty map if it is missing or
+/// unreadable.
+fn load_embedding_cache(path: &str) -> HashMap<String, Vec<f64>> {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Persist the embedding cache sidecar, warning but not failing on write error.
+fn save_embedding_cache(path: &str, cache: &HashMap<String, Vec<f64>>) {
+    match serde_json::to_string(cache) {
+        Ok(json) => {
+            if let Err(e) = fs::write(path, json) {
+                eprintln!("warning: could not write embedding cache {}: {}", path, e);
             }
         }
+        Err(e) => eprintln!("warning: could not serialize embedding cache: {}", e),
+    }
+}
+
+/// Embed every note (reusing cached vectors by content hash) and collect them
+/// into the in-memory vector store.
+async fn build_store(
+    url_embed: &str,
+    model_llm: &str,
+    notes: Vec<(String, Note)>,
+    cache: &mut HashMap<String, Vec<f64>>,
+) -> Result<Vec<EmbeddedNote>, Box<dyn std::error::Error>> {
+    let mut store = Vec::with_capacity(notes.len());
+    for (path, note) in notes {
+        let hash = content_hash(&note.content);
+        let embedding = match cache.get(&hash) {
+            Some(vec) => vec.clone(),
+            None => {
+                let vec = embed(url_embed, model_llm, &note.content).await?;
+                cache.insert(hash, vec.clone());
+                vec
+            }
+        };
+        store.push(EmbeddedNote {
+            path,
+            embedding,
+            note,
+        });
     }
-    files
+    Ok(store)
 }
 
-fn read_md_file(filepath: &str) -> String {
-    let mut file = File::open(filepath).unwrap();
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    contents
+/// Embed the query, rank the store by cosine similarity, and return the content
+/// of the top-`top_k` most relevant notes.
+async fn retrieve_relevant(
+    url_embed: &str,
+    model_llm: &str,
+    query: &str,
+    store: Vec<EmbeddedNote>,
+    top_k: usize,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let query_embedding = embed(url_embed, model_llm, query).await?;
+    let mut ranked: Vec<(f64, EmbeddedNote)> = store
+        .into_iter()
+        .map(|entry| {
+            let score = cosine_similarity(&query_embedding, &entry.embedding);
+            (score, entry)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(ranked
+        .into_iter()
+        .take(top_k)
+        .map(|(score, entry)| {
+            println!("  retrieved {} (score {:.3})", entry.path, score);
+            entry.note.content
+        })
+        .collect())
 }
 
-fn construct_prompt(notes: Vec<Note>) -> String {
-    let note_delimiter = "===\n---\n===\n";
-    let preprompt_summary = format!(
-        "Summarize the following notes delimited by '{}': \n",
-        note_delimiter
-    );
-    let postprompt_summary = "okay now you have all my notes, summarize them for me. and ignore the delimiter please\n";
+/// Cheap token estimate: roughly four characters per token, good enough for
+/// packing decisions without pulling in a real tokenizer.
+fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4
+}
+
+/// The "map" template: wraps one chunk of raw notes and asks for a concise
+/// partial summary of just that batch.
+fn map_prompt(chunk: &str) -> String {
+    format!(
+        "Summarize the following notes delimited by '{delim}': \n{chunk}\n{delim}okay now summarize this batch of my notes for me concisely, and ignore the delimiter please\n",
+        delim = NOTE_DELIMITER,
+        chunk = chunk,
+    )
+}
+
+/// The "reduce" template: wraps a batch of partial summaries and asks for a
+/// single coherent summary merging them.
+fn reduce_prompt(chunk: &str) -> String {
+    format!(
+        "Combine the following partial summaries delimited by '{delim}': \n{chunk}\n{delim}okay now merge these partial summaries into a single coherent summary for me, and ignore the delimiter please\n",
+        delim = NOTE_DELIMITER,
+        chunk = chunk,
+    )
+}
+
+/// Split a single over-budget note on paragraph boundaries so each piece fits
+/// under `budget` estimated tokens. A lone paragraph larger than the budget is
+/// left intact rather than cut mid-sentence.
+fn split_on_paragraphs(text: &str, budget: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut current = String::new();
+    for para in text.split("\n\n") {
+        // Estimate the piece as it would be joined, separator included.
+        if !current.is_empty() && estimate_tokens(&format!("{}\n\n{}", current, para)) > budget {
+            pieces.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(para);
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
 
-    let mut prompt = preprompt_summary;
-    for note in notes {
-        prompt.push_str(&note.content);
-        prompt.push_str(note_delimiter);
+/// Greedily pack texts into chunks whose combined estimate stays under
+/// `budget`, splitting any single text that is itself too large first.
+fn pack_texts(texts: &[String], budget: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for text in texts {
+        let pieces = if estimate_tokens(text) > budget {
+            split_on_paragraphs(text, budget)
+        } else {
+            vec![text.clone()]
+        };
+        for piece in pieces {
+            // Estimate the chunk as it would be sent, delimiter included.
+            if !current.is_empty()
+                && estimate_tokens(&format!("{}{}{}", current, NOTE_DELIMITER, piece)) > budget
+            {
+                chunks.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str(NOTE_DELIMITER);
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Dispatch a single generation either streaming or buffered.
+async fn run_llm(
+    url_llm: &str,
+    model_llm: &str,
+    prompt: &str,
+    stream: bool,
+) -> Result<LLMResponse, Box<dyn std::error::Error>> {
+    if stream {
+        llm_stream(url_llm, model_llm, prompt).await
+    } else {
+        llm(url_llm, model_llm, prompt).await
+    }
+}
+
+/// Hierarchical map-reduce summarizer. Packs `texts` into context-sized chunks,
+/// summarizes each (map), then recursively summarizes the partials (reduce)
+/// until a single chunk remains. The loop is bounded by `max_depth` and by a
+/// non-shrinking guard; both fall back to concatenating everything into one
+/// reduce call so the function always terminates.
+async fn map_reduce_summarize(
+    url_llm: &str,
+    model_llm: &str,
+    stream: bool,
+    budget: usize,
+    max_depth: usize,
+    mut texts: Vec<String>,
+) -> Result<LLMResponse, Box<dyn std::error::Error>> {
+    let map_scaffold = estimate_tokens(&map_prompt(""));
+    let reduce_scaffold = estimate_tokens(&reduce_prompt(""));
+    let mut depth = 0;
+    // Chunk count of the previous round; a round that does not shrink below this
+    // has stalled. Seeded with `MAX` so the first round never trips the guard.
+    let mut prev_chunks = usize::MAX;
+    loop {
+        let scaffold = if depth == 0 { map_scaffold } else { reduce_scaffold };
+        let effective = budget.saturating_sub(scaffold).max(1);
+        let chunks = pack_texts(&texts, effective);
+
+        // A single chunk is the terminating reduce: summarize it and return.
+        if chunks.len() <= 1 {
+            let body = chunks.into_iter().next().unwrap_or_default();
+            let prompt = if depth == 0 {
+                map_prompt(&body)
+            } else {
+                reduce_prompt(&body)
+            };
+            return run_llm(url_llm, model_llm, &prompt, stream).await;
+        }
+
+        // Depth cap or no progress versus the previous round: concatenate
+        // everything and do one reduce. Comparing against the prior round's
+        // chunk count (not this round's input count) lets the split-and-map
+        // path run even for a single over-budget note.
+        if depth + 1 >= max_depth || chunks.len() >= prev_chunks {
+            let body = texts.join(NOTE_DELIMITER);
+            return run_llm(url_llm, model_llm, &reduce_prompt(&body), stream).await;
+        }
+
+        // Map step: summarize each chunk, then reduce over the partials.
+        let mut partials = Vec::with_capacity(chunks.len());
+        for chunk in &chunks {
+            let resp = run_llm(url_llm, model_llm, &map_prompt(chunk), false).await?;
+            partials.push(resp.response);
+        }
+        prev_chunks = chunks.len();
+        texts = partials;
+        depth += 1;
     }
-    prompt.push_str(postprompt_summary);
-    prompt
 }
 
 async fn llm(
@@ -119,31 +550,362 @@ async fn llm(
     }
 }
 
+/// Streaming counterpart to [`llm`]: asks Ollama for `"stream": true` and reads
+/// the NDJSON line-delimited response, printing each `response` fragment to
+/// stdout as it arrives. Returns the accumulated response once a line with
+/// `done: true` is seen.
+async fn llm_stream(
+    url_llm: &str,
+    model_llm: &str,
+    prompt: &str,
+) -> Result<LLMResponse, Box<dyn std::error::Error>> {
+    let client = Client::new();
+    let mut res = client
+        .post(url_llm)
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "model": model_llm,
+            "prompt": prompt,
+            "stream": true,
+        }))
+        .send()
+        .await?;
+
+    if !res.status().is_success() {
+        return Err("Failed to get a valid response from LLM".into());
+    }
+
+    let stdout = std::io::stdout();
+    let mut handle = stdout.lock();
+    let mut buffer = String::new();
+    let mut accumulated = String::new();
+    let mut last: Option<LLMResponse> = None;
+
+    'read: while let Some(chunk) = res.chunk().await? {
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(newline) = buffer.find('\n') {
+            let line: String = buffer.drain(..=newline).collect();
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let partial: LLMPartial = serde_json::from_str(line)?;
+            accumulated.push_str(&partial.response);
+            print!("{}", partial.response);
+            handle.flush()?;
+            let done = partial.done;
+            last = Some(partial.into());
+            if done {
+                break 'read;
+            }
+        }
+    }
+    println!();
+
+    let mut response = last.ok_or("LLM stream ended without any response")?;
+    response.response = accumulated;
+    Ok(response)
+}
+
+/// Rendering mode for the final summary.
+enum OutputFormat {
+    Plain,
+    Json,
+    Markdown,
+}
+
+impl OutputFormat {
+    fn parse(value: &str) -> OutputFormat {
+        match value.to_lowercase().as_str() {
+            "json" => OutputFormat::Json,
+            "markdown" | "md" => OutputFormat::Markdown,
+            _ => OutputFormat::Plain,
+        }
+    }
+}
+
+/// Render the final summary in the requested format. The source-file list is
+/// only used by the markdown renderer.
+fn render_summary(format: &OutputFormat, response: &LLMResponse, sources: &[String]) {
+    match format {
+        OutputFormat::Plain => println!("{}", response.response),
+        OutputFormat::Json => {
+            let out = serde_json::json!({
+                "summary": response.response,
+                "total_duration": response.total_duration,
+                "eval_count": response.eval_count,
+            });
+            println!("{}", serde_json::to_string_pretty(&out).unwrap());
+        }
+        OutputFormat::Markdown => {
+            println!("{}\n\n## Sources\n", response.response);
+            for src in sources {
+                println!("- {}", src);
+            }
+        }
+    }
+}
+
+/// Write `summary` into the `summary:` key of a markdown note's YAML
+/// frontmatter, preserving the body.
+fn write_summary_frontmatter(
+    path: &str,
+    summary: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let raw = fs::read_to_string(path)?;
+    let note = Note::from_string(&raw);
+    let mut map: serde_yaml::Mapping = if note.frontmatter.is_empty() {
+        serde_yaml::Mapping::new()
+    } else {
+        serde_yaml::from_str(&note.frontmatter)?
+    };
+    map.insert(
+        serde_yaml::Value::String("summary".to_string()),
+        serde_yaml::Value::String(summary.to_string()),
+    );
+    let yaml = serde_yaml::to_string(&map)?;
+    fs::write(path, format!("---\n{}---\n{}\n", yaml, note.content))?;
+    Ok(())
+}
+
+/// Seconds since the Unix epoch, for timestamping watch-mode output.
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Snapshot every `.md` file in `dir` to its last-modified time.
+fn md_mtimes(dir: &str) -> HashMap<String, SystemTime> {
+    let mut map = HashMap::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() && path.extension().and_then(|e| e.to_str()) == Some("md") {
+                if let (Some(p), Ok(mtime)) =
+                    (path.to_str(), entry.metadata().and_then(|m| m.modified()))
+                {
+                    map.insert(p.to_string(), mtime);
+                }
+            }
+        }
+    }
+    map
+}
+
+/// Long-running watch loop: poll the notes directory for `.md` changes and
+/// re-run the summarize pipeline whenever a file is created, modified, or
+/// deleted. Only changed files are re-read; rapid successive events are
+/// debounced before a re-summary.
+fn watch(
+    runtime: &tokio::runtime::Runtime,
+    url_llm: &str,
+    model_llm: &str,
+    budget: usize,
+    format: &OutputFormat,
+    dir: &str,
+) {
+    let poll = Duration::from_millis(1000);
+    let debounce = Duration::from_millis(500);
+
+    let mut loaded: HashMap<String, String> = HashMap::new();
+    let summarize_now = |loaded: &HashMap<String, String>| {
+        let mut sources: Vec<String> = loaded.keys().cloned().collect();
+        sources.sort();
+        // Feed notes in sorted-path order so re-runs are deterministic rather
+        // than following HashMap iteration order.
+        let texts: Vec<String> = sources
+            .iter()
+            .filter_map(|path| loaded.get(path).cloned())
+            .collect();
+        let response = runtime
+            .block_on(map_reduce_summarize(
+                url_llm, model_llm, false, budget, MAX_DEPTH, texts,
+            ))
+            .unwrap();
+        println!("[{}] re-summarized {} notes", timestamp(), loaded.len());
+        render_summary(format, &response, &sources);
+    };
+
+    // Initial load and summary.
+    let mut snapshot = md_mtimes(dir);
+    for path in snapshot.keys() {
+        if let Ok(raw) = fs::read_to_string(path) {
+            loaded.insert(path.clone(), Note::from_string(&raw).content);
+        }
+    }
+    summarize_now(&loaded);
+
+    loop {
+        thread::sleep(poll);
+        if md_mtimes(dir) == snapshot {
+            continue;
+        }
+        // Let rapid successive events settle before reacting.
+        thread::sleep(debounce);
+        let current = md_mtimes(dir);
+
+        // Drop deleted files, re-read created or modified ones.
+        loaded.retain(|path, _| current.contains_key(path));
+        for (path, mtime) in &current {
+            if snapshot.get(path) != Some(mtime) {
+                if let Ok(raw) = fs::read_to_string(path) {
+                    loaded.insert(path.clone(), Note::from_string(&raw).content);
+                }
+            }
+        }
+        snapshot = current;
+        summarize_now(&loaded);
+    }
+}
+
+/// Partition notes by tag and emit one summary per tag group, in tag order.
+fn group_by_tag(
+    runtime: &tokio::runtime::Runtime,
+    url_llm: &str,
+    model_llm: &str,
+    budget: usize,
+    format: &OutputFormat,
+    notes: &[(String, Note)],
+) {
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for (_, note) in notes {
+        for tag in note_tags(note) {
+            groups.entry(tag).or_default().push(note.content.clone());
+        }
+    }
+    let mut tags: Vec<String> = groups.keys().cloned().collect();
+    tags.sort();
+    for tag in tags {
+        let texts = groups.remove(&tag).unwrap();
+        let response = runtime
+            .block_on(map_reduce_summarize(
+                url_llm, model_llm, false, budget, MAX_DEPTH, texts,
+            ))
+            .unwrap();
+        println!("\n## {}", tag);
+        render_summary(format, &response, &[]);
+    }
+}
+
+/// Fetch the value following a `--flag` on the command line, if present.
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
 fn main() {
     let url_llm = env::var("URL_LLM").unwrap_or_else(|_| URL_LLM.to_string());
     let model_llm = env::var("MODEL_LLM").unwrap_or_else(|_| MODEL_LLM.to_string());
     let path_to_dir = env::var("PATH_NOTES").unwrap_or_else(|_| PATH_TO_DIR.to_string());
 
+    let args: Vec<String> = env::args().collect();
+    let no_stream = args.iter().any(|arg| arg == "--no-stream")
+        || env::var("STREAM").map(|v| v == "0").unwrap_or(false);
+    let query = flag_value(&args, "--query").or_else(|| env::var("QUERY").ok());
+    let format = OutputFormat::parse(&flag_value(&args, "--format").unwrap_or_default());
+    let write = args.iter().any(|arg| arg == "--write");
+    let watch_mode = args.iter().any(|arg| arg == "--watch");
+    let tag = flag_value(&args, "--tag");
+    let since = flag_value(&args, "--since");
+    let group_by = flag_value(&args, "--group-by");
+
+    let budget = env::var("MAX_CONTEXT_TOKENS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(MAX_CONTEXT_TOKENS);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    if watch_mode {
+        println!("watching {} for changes", path_to_dir);
+        watch(&runtime, &url_llm, &model_llm, budget, &format, &path_to_dir);
+        return;
+    }
+
     println!("loading notes from: {}", path_to_dir);
 
-    let md_files = get_md_files(&path_to_dir);
-    let mut notes = Vec::new();
-    for file in md_files {
-        let content = read_md_file(&file);
-        let note = Note::from_string(&content);
-        notes.push(note);
+    let mut notes = load_notes(&path_to_dir);
+
+    // Frontmatter-aware filtering: keep only notes matching the tag and/or
+    // dated on or after the cutoff date before summarizing.
+    if let Some(ref t) = tag {
+        notes.retain(|(_, note)| note_tags(note).iter().any(|x| x == t));
+    }
+    if let Some(ref s) = since {
+        let cutoff = parse_ymd(s);
+        notes.retain(|(_, note)| {
+            match (note_date(note).as_deref().and_then(parse_ymd), cutoff) {
+                (Some(date), Some(cutoff)) => date >= cutoff,
+                _ => false,
+            }
+        });
     }
 
     println!("got {} notes", notes.len());
 
-    let prompt = construct_prompt(notes);
+    if group_by.as_deref() == Some("tag") {
+        group_by_tag(&runtime, &url_llm, &model_llm, budget, &format, &notes);
+        return;
+    }
 
-    let runtime = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .unwrap();
+    // Unique source paths, in load order, for markdown rendering and write-back.
+    let mut source_files: Vec<String> = Vec::new();
+    for (path, _) in &notes {
+        if !source_files.contains(path) {
+            source_files.push(path.clone());
+        }
+    }
+
+    // RAG mode: retrieve only the notes most relevant to the query, otherwise
+    // summarize everything.
+    let texts: Vec<String> = if let Some(query) = query {
+        let url_embed = url_llm.replace("/api/generate", "/api/embeddings");
+        let top_k = env::var("TOP_K")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(TOP_K);
+        let cache_path = format!("{}/{}", path_to_dir, EMBED_CACHE_FILE);
+        let mut cache = load_embedding_cache(&cache_path);
+        let store = runtime
+            .block_on(build_store(&url_embed, &model_llm, notes, &mut cache))
+            .unwrap();
+        save_embedding_cache(&cache_path, &cache);
+        runtime
+            .block_on(retrieve_relevant(
+                &url_embed, &model_llm, &query, store, top_k,
+            ))
+            .unwrap()
+    } else {
+        notes.into_iter().map(|(_, note)| note.content).collect()
+    };
+    // Streaming only makes sense for the plain renderer; structured formats
+    // need the whole response in hand before emitting anything.
+    let stream = !no_stream && matches!(format, OutputFormat::Plain);
     let response = runtime
-        .block_on(llm(&url_llm, &model_llm, &prompt))
+        .block_on(map_reduce_summarize(
+            &url_llm, &model_llm, stream, budget, MAX_DEPTH, texts,
+        ))
         .unwrap();
-    println!("{}", response.response);
+    // The streaming path already printed the summary as it arrived.
+    if !stream {
+        render_summary(&format, &response, &source_files);
+    }
+
+    if write {
+        for path in &source_files {
+            if !path.ends_with(".md") {
+                continue;
+            }
+            if let Err(e) = write_summary_frontmatter(path, &response.response) {
+                eprintln!("warning: could not write summary to {}: {}", path, e);
+            }
+        }
+    }
 }